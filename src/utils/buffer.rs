@@ -0,0 +1,575 @@
+use std::io;
+
+use crate::style::{Attribute, Color};
+
+/// A single column/row coordinate into a [`TerminalBuffer`], with the origin at the top-left.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Hash)]
+pub struct Point {
+    pub x: u16,
+    pub y: u16,
+}
+
+/// The visual style applied to a [`Cell`]: its colors and active attributes.
+///
+/// This mirrors the subset of SGR parameters crossterm commands can produce; it intentionally
+/// does not attempt to model every attribute combination a real terminal supports.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Style {
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+    pub attributes: Vec<Attribute>,
+}
+
+/// One grid position of a [`TerminalBuffer`]: the grapheme occupying it, and the style it was
+/// written with.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct Cell {
+    pub grapheme: String,
+    pub style: Style,
+}
+
+impl Cell {
+    fn blank() -> Self {
+        Cell {
+            grapheme: " ".to_string(),
+            style: Style::default(),
+        }
+    }
+}
+
+/// An in-memory [`Write`](io::Write) sink that interprets the ANSI escape codes crossterm
+/// commands emit into a styled cell grid.
+///
+/// Point `stdout.queue(...)`/`execute!(...)` calls at a `TerminalBuffer` instead of a real TTY to
+/// assert on rendered output in tests, or to take golden snapshots of a sequence of commands.
+///
+/// ```rust
+/// use std::io::Write;
+/// use crossterm::{cursor::MoveTo, style::Print, utils::buffer::TerminalBuffer, QueueableCommand};
+///
+/// let mut buffer = TerminalBuffer::new(10, 2);
+/// buffer.queue(MoveTo(2, 0)).unwrap();
+/// buffer.queue(Print("hi".to_string())).unwrap();
+/// buffer.flush().unwrap();
+///
+/// assert_eq!(buffer.cell(2, 0).unwrap().grapheme, "h");
+/// ```
+pub struct TerminalBuffer {
+    width: u16,
+    height: u16,
+    cells: Vec<Cell>,
+    cursor: Point,
+    style: Style,
+    parser: Parser,
+}
+
+impl TerminalBuffer {
+    /// Creates a blank buffer of the given size, with the cursor at the origin.
+    pub fn new(width: u16, height: u16) -> Self {
+        TerminalBuffer {
+            width,
+            height,
+            cells: vec![Cell::blank(); usize::from(width) * usize::from(height)],
+            cursor: Point::default(),
+            style: Style::default(),
+            parser: Parser::default(),
+        }
+    }
+
+    /// Resizes the grid, preserving the contents that still fit and clamping the cursor to the
+    /// new bounds. Cells uncovered by a size increase are blank.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        let mut cells = vec![Cell::blank(); usize::from(width) * usize::from(height)];
+
+        for y in 0..self.height.min(height) {
+            for x in 0..self.width.min(width) {
+                let from = self.index(x, y);
+                let to = usize::from(y) * usize::from(width) + usize::from(x);
+                cells[to] = self.cells[from].clone();
+            }
+        }
+
+        self.width = width;
+        self.height = height;
+        self.cells = cells;
+        self.cursor.x = self.cursor.x.min(width.saturating_sub(1));
+        self.cursor.y = self.cursor.y.min(height.saturating_sub(1));
+    }
+
+    /// Returns the cell at `(x, y)`, or `None` if it's out of bounds.
+    pub fn cell(&self, x: u16, y: u16) -> Option<&Cell> {
+        if x < self.width && y < self.height {
+            Some(&self.cells[self.index(x, y)])
+        } else {
+            None
+        }
+    }
+
+    /// The current cursor position.
+    pub fn cursor_position(&self) -> Point {
+        self.cursor
+    }
+
+    /// Iterates over every non-blank cell, yielding its position, style and grapheme.
+    pub fn iter(&self) -> impl Iterator<Item = (Point, &Style, &str)> {
+        self.cells.iter().enumerate().filter_map(move |(i, cell)| {
+            if cell.grapheme == " " && cell.style == Style::default() {
+                None
+            } else {
+                let point = Point {
+                    x: (i % usize::from(self.width)) as u16,
+                    y: (i / usize::from(self.width)) as u16,
+                };
+                Some((point, &cell.style, cell.grapheme.as_str()))
+            }
+        })
+    }
+
+    fn index(&self, x: u16, y: u16) -> usize {
+        usize::from(y) * usize::from(self.width) + usize::from(x)
+    }
+
+    fn put_char(&mut self, c: char) {
+        if self.cells.is_empty() {
+            // A zero-width or zero-height buffer has nowhere to put a cell; drop the character
+            // rather than indexing into an empty grid.
+            return;
+        }
+
+        if self.cursor.x >= self.width {
+            self.cursor.x = 0;
+            self.newline();
+        }
+
+        let index = self.index(self.cursor.x, self.cursor.y);
+        self.cells[index] = Cell {
+            grapheme: c.to_string(),
+            style: self.style.clone(),
+        };
+        self.cursor.x += 1;
+    }
+
+    fn newline(&mut self) {
+        if self.cells.is_empty() {
+            return;
+        }
+
+        if self.cursor.y + 1 >= self.height {
+            self.scroll_up();
+        } else {
+            self.cursor.y += 1;
+        }
+    }
+
+    fn scroll_up(&mut self) {
+        let width = usize::from(self.width);
+        self.cells.drain(0..width);
+        self.cells.resize(width * usize::from(self.height), Cell::blank());
+    }
+
+    fn move_to(&mut self, x: u16, y: u16) {
+        self.cursor.x = x.min(self.width.saturating_sub(1));
+        self.cursor.y = y.min(self.height.saturating_sub(1));
+    }
+
+    fn move_by(&mut self, dx: i32, dy: i32) {
+        let x = (i32::from(self.cursor.x) + dx).max(0) as u16;
+        let y = (i32::from(self.cursor.y) + dy).max(0) as u16;
+        self.move_to(x, y);
+    }
+
+    /// Erases part of the cursor's line, per the `K` mode parameter: `0` (the default) clears from
+    /// the cursor to the end of the line, `1` from the start of the line to the cursor, and `2`
+    /// the entire line.
+    fn erase_line(&mut self, mode: u16) {
+        if self.cells.is_empty() {
+            return;
+        }
+
+        let y = self.cursor.y;
+        // After printing into the last column the cursor sits one past it, awaiting a wrap.
+        let cursor_x = self.cursor.x.min(self.width - 1);
+        let (from, to) = match mode {
+            1 => (0, cursor_x),
+            2 => (0, self.width - 1),
+            _ => (cursor_x, self.width - 1),
+        };
+
+        for x in from..=to {
+            let index = self.index(x, y);
+            self.cells[index] = Cell::blank();
+        }
+    }
+
+    /// Erases part of the screen, per the `J` mode parameter: `0` (the default) clears from the
+    /// cursor to the end of the screen, `1` from the start of the screen to the cursor, and `2`
+    /// (or `3`, which also clears scrollback crossterm doesn't model here) the entire screen.
+    fn erase_display(&mut self, mode: u16) {
+        if self.cells.is_empty() {
+            return;
+        }
+
+        let cursor_index = self.index(self.cursor.x.min(self.width - 1), self.cursor.y);
+        match mode {
+            1 => {
+                for cell in &mut self.cells[..=cursor_index] {
+                    *cell = Cell::blank();
+                }
+            }
+            2 | 3 => self.cells.iter_mut().for_each(|cell| *cell = Cell::blank()),
+            _ => {
+                for cell in &mut self.cells[cursor_index..] {
+                    *cell = Cell::blank();
+                }
+            }
+        }
+    }
+
+    fn apply_sgr(&mut self, params: &[u16]) {
+        if params.is_empty() {
+            self.style = Style::default();
+            return;
+        }
+
+        let mut iter = params.iter().copied();
+        while let Some(param) = iter.next() {
+            match param {
+                0 => self.style = Style::default(),
+                30..=37 => self.style.foreground = Some(ansi_color(param - 30)),
+                40..=47 => self.style.background = Some(ansi_color(param - 40)),
+                38 => self.style.foreground = parse_extended_color(&mut iter),
+                48 => self.style.background = parse_extended_color(&mut iter),
+                attribute => {
+                    if let Some(attribute) = sgr_attribute(attribute) {
+                        self.style.attributes.push(attribute);
+                    }
+                }
+            }
+        }
+    }
+
+    fn dispatch(&mut self, sequence: Sequence) {
+        match sequence {
+            Sequence::Text(c) => match c {
+                '\n' => {
+                    self.cursor.x = 0;
+                    self.newline();
+                }
+                '\r' => self.cursor.x = 0,
+                c => self.put_char(c),
+            },
+            Sequence::CursorPosition(y, x) => self.move_to(x.saturating_sub(1), y.saturating_sub(1)),
+            Sequence::CursorUp(n) => self.move_by(0, -i32::from(n)),
+            Sequence::CursorDown(n) => self.move_by(0, i32::from(n)),
+            Sequence::CursorForward(n) => self.move_by(i32::from(n), 0),
+            Sequence::CursorBack(n) => self.move_by(-i32::from(n), 0),
+            Sequence::EraseLine(mode) => self.erase_line(mode),
+            Sequence::EraseDisplay(mode) => self.erase_display(mode),
+            Sequence::Sgr(params) => self.apply_sgr(&params),
+        }
+    }
+}
+
+impl io::Write for TerminalBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let sequences = self.parser.feed(buf);
+        for sequence in sequences {
+            self.dispatch(sequence);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn ansi_color(index: u16) -> Color {
+    match index {
+        0 => Color::Black,
+        1 => Color::DarkRed,
+        2 => Color::DarkGreen,
+        3 => Color::DarkYellow,
+        4 => Color::DarkBlue,
+        5 => Color::DarkMagenta,
+        6 => Color::DarkCyan,
+        _ => Color::Grey,
+    }
+}
+
+fn parse_extended_color(iter: &mut impl Iterator<Item = u16>) -> Option<Color> {
+    match iter.next()? {
+        5 => {
+            let index = iter.next()?;
+            Some(Color::AnsiValue(index as u8))
+        }
+        2 => {
+            let r = iter.next()? as u8;
+            let g = iter.next()? as u8;
+            let b = iter.next()? as u8;
+            Some(Color::Rgb { r, g, b })
+        }
+        _ => None,
+    }
+}
+
+/// The number of bytes a UTF-8 sequence starting with `first_byte` should occupy, or `None` if
+/// `first_byte` can't start a sequence (e.g. it's a stray continuation byte).
+fn utf8_sequence_len(first_byte: u8) -> Option<usize> {
+    match first_byte {
+        0x00..=0x7f => Some(1),
+        0xc0..=0xdf => Some(2),
+        0xe0..=0xef => Some(3),
+        0xf0..=0xf7 => Some(4),
+        _ => None,
+    }
+}
+
+fn sgr_attribute(code: u16) -> Option<Attribute> {
+    match code {
+        1 => Some(Attribute::Bold),
+        2 => Some(Attribute::Dim),
+        3 => Some(Attribute::Italic),
+        4 => Some(Attribute::Underlined),
+        7 => Some(Attribute::Reverse),
+        8 => Some(Attribute::Hidden),
+        9 => Some(Attribute::CrossedOut),
+        _ => None,
+    }
+}
+
+/// One fully-parsed escape sequence or character of plain text.
+enum Sequence {
+    Text(char),
+    CursorPosition(u16, u16),
+    CursorUp(u16),
+    CursorDown(u16),
+    CursorForward(u16),
+    CursorBack(u16),
+    EraseLine(u16),
+    EraseDisplay(u16),
+    Sgr(Vec<u16>),
+}
+
+/// An incremental parser that buffers a partially-received CSI escape sequence, or a
+/// partially-received UTF-8 character, across `write` calls, since crossterm may split a single
+/// command's (or a single grapheme's) bytes across multiple writes.
+#[derive(Default)]
+struct Parser {
+    pending: Vec<u8>,
+    in_escape: bool,
+    utf8_pending: Vec<u8>,
+}
+
+impl Parser {
+    fn feed(&mut self, buf: &[u8]) -> Vec<Sequence> {
+        let mut out = Vec::new();
+
+        for &byte in buf {
+            if self.in_escape {
+                self.pending.push(byte);
+                if byte.is_ascii_alphabetic() || byte == b'~' {
+                    self.in_escape = false;
+                    if let Some(sequence) = self.parse_csi() {
+                        out.push(sequence);
+                    }
+                    self.pending.clear();
+                }
+            } else if byte == 0x1b {
+                if !self.utf8_pending.is_empty() {
+                    // A UTF-8 sequence cut short by an escape is invalid.
+                    self.utf8_pending.clear();
+                    out.push(Sequence::Text(char::REPLACEMENT_CHARACTER));
+                }
+                self.in_escape = true;
+                self.pending.clear();
+            } else if let Some(c) = self.decode_utf8_byte(byte) {
+                out.push(Sequence::Text(c));
+            }
+        }
+
+        out
+    }
+
+    /// Feeds one byte of plain text through the UTF-8 decoder, returning the decoded `char` once
+    /// a full, valid sequence has been buffered. Bytes that don't form valid UTF-8 are replaced
+    /// with `U+FFFD` rather than silently reinterpreting each raw byte as its own scalar value.
+    fn decode_utf8_byte(&mut self, byte: u8) -> Option<char> {
+        if self.utf8_pending.is_empty() {
+            match utf8_sequence_len(byte) {
+                Some(1) => Some(byte as char),
+                Some(_) => {
+                    self.utf8_pending.push(byte);
+                    None
+                }
+                None => Some(char::REPLACEMENT_CHARACTER),
+            }
+        } else {
+            self.utf8_pending.push(byte);
+
+            let expected = utf8_sequence_len(self.utf8_pending[0]).unwrap_or(1);
+            if self.utf8_pending.len() < expected {
+                return None;
+            }
+
+            let decoded = std::str::from_utf8(&self.utf8_pending)
+                .ok()
+                .and_then(|s| s.chars().next())
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            self.utf8_pending.clear();
+            Some(decoded)
+        }
+    }
+
+    /// Parses a buffered CSI sequence of the form `[<params>]<final>` (the leading ESC and `[`
+    /// have already been consumed).
+    fn parse_csi(&self) -> Option<Sequence> {
+        let bytes = &self.pending;
+        if bytes.first() != Some(&b'[') {
+            return None;
+        }
+
+        let final_byte = *bytes.last()?;
+        let params_str = std::str::from_utf8(&bytes[1..bytes.len() - 1]).ok()?;
+        let params: Vec<u16> = if params_str.is_empty() {
+            Vec::new()
+        } else {
+            params_str
+                .split(';')
+                .map(|p| p.parse().unwrap_or(0))
+                .collect()
+        };
+        let param = |default: u16| params.first().copied().unwrap_or(default);
+
+        match final_byte {
+            b'H' | b'f' => Some(Sequence::CursorPosition(param(1), *params.get(1).unwrap_or(&1))),
+            b'A' => Some(Sequence::CursorUp(param(1))),
+            b'B' => Some(Sequence::CursorDown(param(1))),
+            b'C' => Some(Sequence::CursorForward(param(1))),
+            b'D' => Some(Sequence::CursorBack(param(1))),
+            b'K' => Some(Sequence::EraseLine(param(0))),
+            b'J' => Some(Sequence::EraseDisplay(param(0))),
+            b'm' => Some(Sequence::Sgr(params)),
+            _ => None,
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn move_to_and_print_round_trip() {
+        let mut buffer = TerminalBuffer::new(10, 2);
+        buffer.write_all(b"\x1B[1;3Hhi").unwrap();
+
+        assert_eq!(buffer.cell(2, 0).unwrap().grapheme, "h");
+        assert_eq!(buffer.cell(3, 0).unwrap().grapheme, "i");
+        assert_eq!(buffer.cursor_position(), Point { x: 4, y: 0 });
+    }
+
+    #[test]
+    fn csi_sequence_split_across_writes_still_parses() {
+        let mut buffer = TerminalBuffer::new(10, 2);
+        buffer.write_all(b"\x1B[1").unwrap();
+        buffer.write_all(b";3H").unwrap();
+        buffer.write_all(b"x").unwrap();
+
+        assert_eq!(buffer.cell(2, 0).unwrap().grapheme, "x");
+    }
+
+    #[test]
+    fn multi_byte_utf8_split_across_writes_decodes_to_one_grapheme() {
+        let mut buffer = TerminalBuffer::new(10, 2);
+        let bytes = "é".as_bytes();
+        buffer.write_all(&bytes[..1]).unwrap();
+        buffer.write_all(&bytes[1..]).unwrap();
+
+        assert_eq!(buffer.cell(0, 0).unwrap().grapheme, "é");
+    }
+
+    #[test]
+    fn invalid_utf8_becomes_replacement_character() {
+        let mut buffer = TerminalBuffer::new(10, 2);
+        buffer.write_all(&[0xff]).unwrap();
+
+        assert_eq!(buffer.cell(0, 0).unwrap().grapheme, "\u{FFFD}");
+    }
+
+    #[test]
+    fn text_wraps_at_right_margin() {
+        let mut buffer = TerminalBuffer::new(3, 2);
+        buffer.write_all(b"abcd").unwrap();
+
+        assert_eq!(buffer.cell(2, 0).unwrap().grapheme, "c");
+        assert_eq!(buffer.cell(0, 1).unwrap().grapheme, "d");
+    }
+
+    #[test]
+    fn writes_past_bottom_scroll_the_grid_up() {
+        let mut buffer = TerminalBuffer::new(3, 2);
+        buffer.write_all(b"abc\r\ndef\r\nghi").unwrap();
+
+        assert_eq!(buffer.cell(0, 0).unwrap().grapheme, "d");
+        assert_eq!(buffer.cell(0, 1).unwrap().grapheme, "g");
+    }
+
+    #[test]
+    fn erase_line_default_mode_clears_from_cursor_to_end() {
+        let mut buffer = TerminalBuffer::new(5, 1);
+        buffer.write_all(b"abcde\x1B[1;3H").unwrap();
+
+        buffer.write_all(b"\x1B[K").unwrap();
+        assert_eq!(buffer.cell(0, 0).unwrap().grapheme, "a");
+        assert_eq!(buffer.cell(1, 0).unwrap().grapheme, "b");
+        assert_eq!(buffer.cell(2, 0).unwrap().grapheme, " ");
+        assert_eq!(buffer.cell(4, 0).unwrap().grapheme, " ");
+    }
+
+    #[test]
+    fn erase_line_mode_one_clears_from_start_to_cursor() {
+        let mut buffer = TerminalBuffer::new(5, 1);
+        buffer.write_all(b"abcde\x1B[1;3H\x1B[1K").unwrap();
+
+        assert_eq!(buffer.cell(0, 0).unwrap().grapheme, " ");
+        assert_eq!(buffer.cell(2, 0).unwrap().grapheme, " ");
+        assert_eq!(buffer.cell(3, 0).unwrap().grapheme, "d");
+    }
+
+    #[test]
+    fn erase_display_mode_two_clears_everything() {
+        let mut buffer = TerminalBuffer::new(3, 2);
+        buffer.write_all(b"abcdef\x1B[2J").unwrap();
+
+        for y in 0..2 {
+            for x in 0..3 {
+                assert_eq!(buffer.cell(x, y).unwrap().grapheme, " ");
+            }
+        }
+    }
+
+    #[test]
+    fn sgr_color_is_tracked_on_the_cell() {
+        let mut buffer = TerminalBuffer::new(5, 1);
+        buffer.write_all(b"\x1B[31mx").unwrap();
+
+        assert_eq!(buffer.cell(0, 0).unwrap().style.foreground, Some(Color::DarkRed));
+    }
+
+    #[test]
+    fn zero_width_buffer_does_not_panic() {
+        let mut buffer = TerminalBuffer::new(0, 3);
+        buffer.write_all(b"hello\n\r").unwrap();
+        assert!(buffer.cell(0, 0).is_none());
+    }
+
+    #[test]
+    fn zero_height_buffer_does_not_panic() {
+        let mut buffer = TerminalBuffer::new(3, 0);
+        buffer.write_all(b"hello\n\r\x1B[K\x1B[J").unwrap();
+        assert!(buffer.cell(0, 0).is_none());
+    }
+}
@@ -1,8 +1,10 @@
-use std::{fmt::Display, io::Write};
+use std::{
+    cell::Cell,
+    fmt::{self, Display},
+    io::Write,
+};
 
-use crate::{execute, queue};
-
-use super::error::Result;
+use super::error::{ErrorKind, Result};
 
 /// An interface for a command that can be entered on the terminal.
 ///
@@ -18,6 +20,33 @@ pub trait Command {
     /// **This method is used internally by crossterm, and should not be called manually!**
     fn ansi_code(&self) -> Self::AnsiType;
 
+    /// Writes this command's ANSI representation straight into `out`.
+    ///
+    /// This is the primitive the blanket [`queue`](QueueableCommand::queue)/[`execute`](ExecutableCommand::execute)
+    /// impls below call. Commands with dynamic content (`Print`, cursor moves, SGR sequences, ...)
+    /// can override it to format directly into the caller's buffer, so writing many queued
+    /// commands into one `io::Write` costs zero intermediate allocation.
+    ///
+    /// The default implementation is a shim over [`ansi_code`](Command::ansi_code), kept so
+    /// existing commands written against the old `AnsiType`/`ansi_code` API keep working
+    /// unmodified during the migration to this trait.
+    ///
+    /// **This method is used internally by crossterm, and should not be called manually!**
+    fn write_ansi(&self, out: &mut impl fmt::Write) -> fmt::Result {
+        write!(out, "{}", self.ansi_code())
+    }
+
+    /// Renders this command's ANSI representation to an owned `String`.
+    ///
+    /// Useful for caching a command's output, splicing it into a larger pre-rendered frame,
+    /// logging it, or sending it over a socket, without going through a terminal at all.
+    fn to_ansi_string(&self) -> String {
+        let mut buffer = String::new();
+        self.write_ansi(&mut buffer)
+            .expect("writing to a String cannot fail");
+        buffer
+    }
+
     /// Execute this command.
     ///
     /// Windows versions lower than windows 10 do not support ANSI escape codes, therefore a direct WinAPI call is made.
@@ -27,6 +56,88 @@ pub trait Command {
     fn execute_winapi(&self) -> Result<()>;
 }
 
+/// Adapts an [`io::Write`](Write) so a [`Command`] can [`write_ansi`](Command::write_ansi) into it
+/// without an intermediate `String` allocation.
+///
+/// `fmt::Write::write_str` can't report the underlying I/O error directly (it only has
+/// [`fmt::Error`], a zero-information marker), so the real error is stashed in `result` and
+/// recovered by the caller once formatting bails out.
+struct IoWriteAdapter<'a, W: Write> {
+    writer: &'a mut W,
+    result: std::io::Result<()>,
+}
+
+impl<'a, W: Write> fmt::Write for IoWriteAdapter<'a, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        match self.writer.write_all(s.as_bytes()) {
+            Ok(()) => Ok(()),
+            Err(error) => {
+                self.result = Err(error);
+                Err(fmt::Error)
+            }
+        }
+    }
+}
+
+thread_local! {
+    // `write_ansi` can only report a failed side effect as a bare `fmt::Error`, which carries no
+    // information. A command whose `write_ansi` performs something other than writing bytes (e.g.
+    // a fallible syscall) can stash the real error here immediately before returning that
+    // `fmt::Error`, so `write_command` can recover it instead of falling back to a generic message.
+    static STASHED_COMMAND_ERROR: Cell<Option<ErrorKind>> = const { Cell::new(None) };
+}
+
+/// Stashes `error` as the cause of the `fmt::Error` [`write_ansi`](Command::write_ansi) is about
+/// to return, so [`write_command`] can recover it afterwards.
+///
+/// Commands that override `write_ansi` to perform something other than writing bytes into `out`
+/// (for example [`EnableRawMode`](crate::utils::EnableRawMode), which calls `tcsetattr`) should
+/// call this with the real error immediately before returning `Err(fmt::Error)`.
+pub(crate) fn stash_command_error(error: ErrorKind) {
+    STASHED_COMMAND_ERROR.with(|cell| cell.set(Some(error)));
+}
+
+fn take_stashed_command_error() -> Option<ErrorKind> {
+    STASHED_COMMAND_ERROR.with(|cell| cell.take())
+}
+
+/// Writes `command`'s ANSI representation straight into `writer`, falling back to the WinAPI path
+/// on Windows versions that don't understand ANSI escape codes.
+///
+/// This is the same zero-allocation path `queue`/`execute` use below; other callers that write a
+/// command onto an `io::Write` (the reversible-command guards, the `Terminal` facade) should go
+/// through this rather than rolling their own `to_ansi_string` + `write!`.
+pub(crate) fn write_command<W, C>(writer: &mut W, command: &C) -> Result<()>
+where
+    W: Write,
+    C: Command,
+{
+    #[cfg(windows)]
+    {
+        if !crate::utils::supports_ansi() {
+            return command.execute_winapi();
+        }
+    }
+
+    let mut adapter = IoWriteAdapter {
+        writer,
+        result: Ok(()),
+    };
+
+    match command.write_ansi(&mut adapter) {
+        Ok(()) => Ok(()),
+        // `write_ansi` can fail for three reasons, in the order checked below: the adapter's
+        // `write_str` hit a real I/O error (captured in `adapter.result`); the command stashed the
+        // real cause of a non-I/O failure via `stash_command_error` before returning; or neither
+        // happened and the command's `fmt::Error` carries no information at all.
+        Err(_) => Err(match adapter.result {
+            Err(io_error) => io_error.into(),
+            Ok(()) => take_stashed_command_error()
+                .unwrap_or_else(|| std::io::Error::other("command failed").into()),
+        }),
+    }
+}
+
 /// An interface for commands that can be executed in the near future.
 pub trait QueueableCommand<T: Display>: Sized {
     /// Queues the given command for execution in the near future.
@@ -91,7 +202,7 @@ where
     /// The reason for this is that Windows versions lower than 10 do not support ANSI codes, and can therefore not be written to the given `writer`.
     /// Therefore, there is no difference between [execute](./trait.ExecutableCommand.html) and [queue](./trait.QueueableCommand.html) for those old Windows versions.
     fn queue(&mut self, command: impl Command<AnsiType = A>) -> Result<&mut Self> {
-        queue!(self, command)?;
+        write_command(self, &command)?;
         Ok(self)
     }
 }
@@ -136,7 +247,8 @@ where
     /// The reason for this is that Windows versions lower than 10 do not support ANSI codes, and can therefore not be written to the given `writer`.
     /// Therefore, there is no difference between [execute](./trait.ExecutableCommand.html) and [queue](./trait.QueueableCommand.html) for those old Windows versions.
     fn execute(&mut self, command: impl Command<AnsiType = A>) -> Result<&mut Self> {
-        execute!(self, command)?;
+        write_command(self, &command)?;
+        self.flush()?;
         Ok(self)
     }
 }
@@ -0,0 +1,173 @@
+use std::io::{self, Read, Write};
+
+use super::command::{write_command, Command};
+use super::error::Result;
+
+/// A piece of terminal state that can be asked for via [`Terminal::get`].
+pub enum Value {
+    /// The current cursor position.
+    CursorPosition,
+    /// The size of the terminal, in columns and rows.
+    TerminalSize,
+    /// Whether the terminal understands ANSI escape codes.
+    SupportsAnsi,
+    /// Whether the terminal supports 24-bit RGB colors.
+    SupportsTrueColor,
+}
+
+/// The result of a [`Terminal::get`] query, matching the [`Value`] that was asked for.
+pub enum Retrieved {
+    CursorPosition(u16, u16),
+    TerminalSize(u16, u16),
+    SupportsAnsi(bool),
+    SupportsTrueColor(bool),
+}
+
+/// A facade unifying command execution and terminal state queries behind one type.
+///
+/// Today, writing commands goes through [`QueueableCommand`](super::command::QueueableCommand)/
+/// [`ExecutableCommand`](super::command::ExecutableCommand), while reading state is a grab bag of
+/// free functions (`cursor::position()`, `terminal::size()`, ...). `Terminal` wraps a writer and
+/// offers both: [`act`](Terminal::act) and [`batch`](Terminal::batch) mirror `execute`/`queue`,
+/// and [`get`](Terminal::get) issues the matching device-status query (or, on Windows versions
+/// without ANSI support, the equivalent WinAPI call) and parses the reply.
+pub struct Terminal<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> Terminal<W> {
+    /// Wraps `writer` in a `Terminal` facade.
+    pub fn new(writer: W) -> Self {
+        Terminal { writer }
+    }
+
+    /// Unwraps this facade, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    /// Executes `command` immediately.
+    pub fn act<C: Command>(&mut self, command: C) -> Result<()> {
+        write_command(&mut self.writer, &command)?;
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Buffers `command` for execution on the next [`flush_batch`](Terminal::flush_batch).
+    pub fn batch<C: Command>(&mut self, command: C) -> Result<()> {
+        write_command(&mut self.writer, &command)?;
+        Ok(())
+    }
+
+    /// Flushes every command queued by [`batch`](Terminal::batch).
+    pub fn flush_batch(&mut self) -> Result<()> {
+        self.writer.flush()?;
+        Ok(())
+    }
+
+    /// Queries the terminal for `value`, blocking until it replies.
+    ///
+    /// [`Value::TerminalSize`] prefers the ioctl-based [`terminal::size`](crate::terminal::size)
+    /// helper, which answers instantly without touching stdin. Otherwise, on UNIX and Windows
+    /// 10+, this writes the matching DSR (device status report) escape sequence and parses the
+    /// reply read back from stdin. On older Windows versions, which do not understand ANSI codes,
+    /// a direct WinAPI call is made instead.
+    pub fn get(&mut self, value: Value) -> Result<Retrieved> {
+        #[cfg(windows)]
+        {
+            if let Some(retrieved) = Self::get_winapi(&value)? {
+                return Ok(retrieved);
+            }
+        }
+
+        match value {
+            Value::CursorPosition => {
+                write!(self.writer, "\x1B[6n")?;
+                self.writer.flush()?;
+                let (row, col) = read_cursor_position_reply()?;
+                Ok(Retrieved::CursorPosition(col, row))
+            }
+            Value::TerminalSize => {
+                // `terminal::size()` answers from an ioctl, with no stdin round-trip and no
+                // assumption that raw mode is already on, so prefer it whenever it's available
+                // and only fall back to the DSR probe below if it fails (e.g. `writer` isn't
+                // backed by a real terminal device).
+                if let Ok((cols, rows)) = crate::terminal::size() {
+                    return Ok(Retrieved::TerminalSize(cols, rows));
+                }
+
+                // Moving to an absurdly large position and asking where the cursor landed is the
+                // classic ANSI-only way to discover the terminal size without a WinAPI call or an
+                // ioctl. `\x1B[s`/`\x1B[u` save and restore the cursor around the probe so this
+                // query doesn't leave the real cursor parked in the bottom-right corner.
+                write!(self.writer, "\x1B[s\x1B[999;999H\x1B[6n")?;
+                self.writer.flush()?;
+                let (rows, cols) = read_cursor_position_reply()?;
+                write!(self.writer, "\x1B[u")?;
+                self.writer.flush()?;
+                Ok(Retrieved::TerminalSize(cols, rows))
+            }
+            Value::SupportsAnsi => Ok(Retrieved::SupportsAnsi(crate::utils::supports_ansi())),
+            Value::SupportsTrueColor => Ok(Retrieved::SupportsTrueColor(
+                std::env::var("COLORTERM")
+                    .map(|value| value == "truecolor" || value == "24bit")
+                    .unwrap_or(false),
+            )),
+        }
+    }
+
+    /// Versions of Windows prior to 10 do not support ANSI escape codes; on those, each [`Value`]
+    /// is retrieved through the WinAPI console functions instead of a DSR query.
+    ///
+    /// Returns `Ok(None)` when the console does understand ANSI (Windows 10+), so [`get`](Terminal::get)
+    /// falls through to the DSR path above.
+    #[cfg(windows)]
+    fn get_winapi(value: &Value) -> Result<Option<Retrieved>> {
+        use crossterm_winapi::ScreenBuffer;
+
+        if crate::utils::supports_ansi() {
+            return Ok(None);
+        }
+
+        match value {
+            Value::CursorPosition => {
+                let position = ScreenBuffer::current()?.info()?.cursor_pos();
+                Ok(Some(Retrieved::CursorPosition(position.x as u16, position.y as u16)))
+            }
+            Value::TerminalSize => {
+                let size = ScreenBuffer::current()?.info()?.terminal_size();
+                Ok(Some(Retrieved::TerminalSize(size.width as u16, size.height as u16)))
+            }
+            Value::SupportsAnsi => Ok(Some(Retrieved::SupportsAnsi(false))),
+            Value::SupportsTrueColor => Ok(Some(Retrieved::SupportsTrueColor(false))),
+        }
+    }
+}
+
+/// Reads a `ESC [ <row> ; <col> R` cursor position report from stdin.
+fn read_cursor_position_reply() -> Result<(u16, u16)> {
+    let mut reply = Vec::new();
+    let mut byte = [0u8; 1];
+    let mut stdin = io::stdin();
+
+    loop {
+        stdin.read_exact(&mut byte)?;
+        reply.push(byte[0]);
+        if byte[0] == b'R' {
+            break;
+        }
+    }
+
+    parse_cursor_position_reply(&reply).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "malformed cursor position reply").into()
+    })
+}
+
+fn parse_cursor_position_reply(reply: &[u8]) -> Option<(u16, u16)> {
+    let reply = std::str::from_utf8(reply).ok()?;
+    let reply = reply.strip_prefix("\x1B[")?.strip_suffix('R')?;
+    let mut parts = reply.split(';');
+    let row = parts.next()?.parse().ok()?;
+    let col = parts.next()?.parse().ok()?;
+    Some((row, col))
+}
@@ -0,0 +1,210 @@
+use std::io::Write;
+
+use super::command::{stash_command_error, write_command, Command};
+use super::error::Result;
+
+/// A [`Command`] that knows how to undo itself.
+///
+/// This is the building block of [`ScopedCommand`]: pairing a command with its inverse lets
+/// `execute_scoped` restore the terminal to the state it was in before the command ran, even if
+/// the code in between panics. Implement this for any command that has a natural opposite (for
+/// example entering/leaving the alternate screen, or hiding/showing the cursor).
+pub trait ReversibleCommand: Command {
+    /// The command that undoes this one.
+    type Undo: Command;
+
+    /// Returns the command that restores the terminal to the state it was in before this command
+    /// was executed.
+    fn undo(&self) -> Self::Undo;
+}
+
+/// A guard returned by [`execute_scoped`](ScopedExecutableCommand::execute_scoped).
+///
+/// The wrapped command's [`undo`](ReversibleCommand::undo) is executed and flushed when this
+/// guard is dropped, so the terminal is restored even if a panic unwinds through the scope.
+pub struct ScopedCommand<'a, W: Write, C: ReversibleCommand> {
+    writer: &'a mut W,
+    command: C,
+}
+
+impl<'a, W, C> Drop for ScopedCommand<'a, W, C>
+where
+    W: Write,
+    C: ReversibleCommand,
+{
+    /// Writes and flushes this command's undo, restoring the terminal state.
+    ///
+    /// Errors encountered while undoing are silently ignored: a `Drop` impl has no way to
+    /// propagate them, and leaving the user's terminal in a recoverable state takes priority over
+    /// reporting the write failure.
+    fn drop(&mut self) {
+        let _ = write_command(self.writer, &self.command.undo());
+        let _ = self.writer.flush();
+    }
+}
+
+/// An interface for commands that are executed immediately, together with their inverse.
+pub trait ScopedExecutableCommand<C: ReversibleCommand>: Write + Sized {
+    /// Executes `command` now, and returns a guard that executes and flushes `command.undo()`
+    /// when dropped.
+    ///
+    /// This removes the common footgun of an early return or panic between, for example, entering
+    /// the alternate screen and the matching "leave" call: the terminal is always restored once
+    /// the guard goes out of scope.
+    ///
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// use std::io::stdout;
+    /// use crossterm::{Result, terminal::EnterAlternateScreen, utils::ScopedExecutableCommand};
+    ///
+    /// fn main() -> Result<()> {
+    ///     let mut stdout = stdout();
+    ///     let _screen = stdout.execute_scoped(EnterAlternateScreen)?;
+    ///
+    ///     // ... draw the alternate screen ...
+    ///
+    ///     Ok(())
+    ///     // `_screen` is dropped here, writing `LeaveAlternateScreen` and flushing.
+    /// }
+    /// ```
+    fn execute_scoped(&mut self, command: C) -> Result<ScopedCommand<'_, Self, C>>;
+}
+
+impl<W, C> ScopedExecutableCommand<C> for W
+where
+    W: Write,
+    C: ReversibleCommand,
+{
+    fn execute_scoped(&mut self, command: C) -> Result<ScopedCommand<'_, Self, C>> {
+        write_command(self, &command)?;
+        self.flush()?;
+        Ok(ScopedCommand {
+            writer: self,
+            command,
+        })
+    }
+}
+
+use crate::cursor::{Hide, Show};
+use crate::event::{DisableMouseCapture, EnableMouseCapture};
+use crate::terminal::{EnterAlternateScreen, LeaveAlternateScreen};
+
+impl ReversibleCommand for EnterAlternateScreen {
+    type Undo = LeaveAlternateScreen;
+
+    fn undo(&self) -> Self::Undo {
+        LeaveAlternateScreen
+    }
+}
+
+impl ReversibleCommand for LeaveAlternateScreen {
+    type Undo = EnterAlternateScreen;
+
+    fn undo(&self) -> Self::Undo {
+        EnterAlternateScreen
+    }
+}
+
+impl ReversibleCommand for Hide {
+    type Undo = Show;
+
+    fn undo(&self) -> Self::Undo {
+        Show
+    }
+}
+
+impl ReversibleCommand for Show {
+    type Undo = Hide;
+
+    fn undo(&self) -> Self::Undo {
+        Hide
+    }
+}
+
+impl ReversibleCommand for EnableMouseCapture {
+    type Undo = DisableMouseCapture;
+
+    fn undo(&self) -> Self::Undo {
+        DisableMouseCapture
+    }
+}
+
+impl ReversibleCommand for DisableMouseCapture {
+    type Undo = EnableMouseCapture;
+
+    fn undo(&self) -> Self::Undo {
+        EnableMouseCapture
+    }
+}
+
+/// A [`Command`] that enables raw mode, pairing the free [`enable_raw_mode`](crate::terminal::enable_raw_mode)
+/// function with the [`Command`]/[`ReversibleCommand`] API so it can be used with `execute_scoped`.
+///
+/// Unlike most commands, raw mode is not toggled by an ANSI escape sequence on UNIX either, so
+/// `ansi_code` is never actually called: [`write_ansi`](Command::write_ansi) is overridden to
+/// perform the `termios` call itself and reports failure (not a TTY, `tcsetattr` failing, ...) as
+/// a real `Err`, instead of swallowing it the way discarding a `Result` behind `ansi_code`'s
+/// infallible `Display` return type would.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct EnableRawMode;
+
+/// A [`Command`] that disables raw mode. The inverse of [`EnableRawMode`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct DisableRawMode;
+
+impl Command for EnableRawMode {
+    type AnsiType = &'static str;
+
+    fn ansi_code(&self) -> Self::AnsiType {
+        ""
+    }
+
+    fn write_ansi(&self, _out: &mut impl std::fmt::Write) -> std::fmt::Result {
+        crate::terminal::enable_raw_mode().map_err(|error| {
+            stash_command_error(error);
+            std::fmt::Error
+        })
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> Result<()> {
+        crate::terminal::enable_raw_mode()
+    }
+}
+
+impl Command for DisableRawMode {
+    type AnsiType = &'static str;
+
+    fn ansi_code(&self) -> Self::AnsiType {
+        ""
+    }
+
+    fn write_ansi(&self, _out: &mut impl std::fmt::Write) -> std::fmt::Result {
+        crate::terminal::disable_raw_mode().map_err(|error| {
+            stash_command_error(error);
+            std::fmt::Error
+        })
+    }
+
+    #[cfg(windows)]
+    fn execute_winapi(&self) -> Result<()> {
+        crate::terminal::disable_raw_mode()
+    }
+}
+
+impl ReversibleCommand for EnableRawMode {
+    type Undo = DisableRawMode;
+
+    fn undo(&self) -> Self::Undo {
+        DisableRawMode
+    }
+}
+
+impl ReversibleCommand for DisableRawMode {
+    type Undo = EnableRawMode;
+
+    fn undo(&self) -> Self::Undo {
+        EnableRawMode
+    }
+}